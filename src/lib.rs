@@ -8,12 +8,17 @@
 //! Fast and lightweight Slab Allocator.
 
 
-extern crate libc;
+#![no_std]
 
 
-use std::{mem, ptr};
-use std::ops::{Drop, Index};
-use std::iter::{Iterator, IntoIterator};
+extern crate alloc;
+
+
+use core::{mem, ptr};
+use core::ops::{Drop, Index};
+use core::iter::{Iterator, IntoIterator, FromIterator, Extend};
+use core::ptr::NonNull;
+use alloc::alloc::{alloc, realloc, dealloc, Layout};
 
 
 pub struct Slab<T> {
@@ -41,11 +46,25 @@ impl<T> Slab<T> {
     ///
     /// Panics if the host system is out of memory
     pub fn with_capacity(capacity: usize) -> Slab<T> {
-        let maybe_ptr = unsafe {
-            libc::malloc((mem::size_of::<T>() * capacity)) as *mut T
+        // Zero-sized types never touch the allocator; a dangling-but-aligned
+        // pointer stands in for the backing store and capacity is effectively
+        // unbounded, mirroring how `Vec` handles ZSTs.
+        if mem::size_of::<T>() == 0 {
+            return Slab {
+                capacity: usize::MAX,
+                len: 0,
+                mem: NonNull::<T>::dangling().as_ptr()
+            }
+        }
+
+        let layout = Self::layout(capacity);
+        let maybe_ptr = if capacity != 0 {
+            unsafe { alloc(layout) as *mut T }
+        } else {
+            NonNull::<T>::dangling().as_ptr()
         };
 
-        // malloc will return NULL if called with zero
+        // The allocator returns NULL on failure
         if maybe_ptr.is_null() && capacity != 0 {
             panic!("Unable to allocate requested capacity")
         }
@@ -57,6 +76,29 @@ impl<T> Slab<T> {
         }
     }
 
+    /// Returns the `Layout` describing `capacity` elements of `T`.
+    #[inline]
+    fn layout(capacity: usize) -> Layout {
+        Layout::from_size_align(mem::size_of::<T>() * capacity, mem::align_of::<T>())
+            .expect("Invalid layout for requested capacity")
+    }
+
+    /// Creates a Slab of `n` elements, populating slot `i` with `f(i)`.
+    ///
+    /// The backing store is allocated exactly once, up front.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the host system is out of memory
+    pub fn from_fn<F: FnMut(usize) -> T>(n: usize, mut f: F) -> Slab<T> {
+        let mut slab: Slab<T> = Slab::with_capacity(n);
+        for i in 0..n {
+            unsafe { ptr::write(slab.mem.offset(i as isize), f(i)); }
+            slab.len += 1;
+        }
+        slab
+    }
+
     /// Inserts a new element into the slab, re-allocating if neccessary.
     ///
     /// # Panics
@@ -83,30 +125,90 @@ impl<T> Slab<T> {
         assert!(offset < self.len, "Offset out of bounds");
 
         let elem: T;
-        let last_elem: T;
-        let elem_ptr: *mut T;
-        let last_elem_ptr: *mut T;
 
         unsafe {
-            elem_ptr = self.mem.offset(offset as isize);
-            last_elem_ptr = self.mem.offset(self.len as isize);
+            let elem_ptr = self.mem.offset(offset as isize);
+            let last_elem_ptr = self.mem.offset((self.len - 1) as isize);
 
             elem = ptr::read(elem_ptr);
-            last_elem = ptr::read(last_elem_ptr);
-
-            ptr::write(elem_ptr, last_elem);
 
-            // ptr::swap(elem_ptr, last_elem_ptr);
+            // Move the true last element into the vacated slot. When removing
+            // the last element there is nothing to move, so the copy is skipped.
+            if elem_ptr != last_elem_ptr {
+                ptr::write(elem_ptr, ptr::read(last_elem_ptr));
+            }
         }
 
         self.len -= 1;
         return elem;
     }
 
+    /// Returns a reference to the element at `index`, or `None` if out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index < self.len {
+            unsafe { Some(&*self.mem.offset(index as isize)) }
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the element at `index`, or `None` if out
+    /// of bounds.
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index < self.len {
+            unsafe { Some(&mut *self.mem.offset(index as isize)) }
+        } else {
+            None
+        }
+    }
+
+    /// Removes and returns the last element, or `None` if the slab is empty.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 { return None; }
+
+        self.len -= 1;
+        unsafe { Some(ptr::read(self.mem.offset(self.len as isize))) }
+    }
+
     /// Returns the number of elements in the slab
     #[inline]
     pub fn len(&self) -> usize { self.len }
 
+    /// Returns `true` if the slab contains no elements
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// Drops all elements, resetting `len` to 0 while keeping the allocation.
+    pub fn clear(&mut self) {
+        for x in 0..self.len {
+            unsafe { ptr::drop_in_place(self.mem.offset(x as isize)); }
+        }
+        self.len = 0;
+    }
+
+    /// Grows the capacity to hold at least `additional` more elements,
+    /// allocating in a single step.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the host system is out of memory
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len + additional;
+        if needed > self.capacity { self.resize_to(needed); }
+    }
+
+    /// Reallocates the backing store down to exactly `len` elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the host system is out of memory
+    pub fn shrink_to_fit(&mut self) {
+        if self.len < self.capacity { self.resize_to(self.len); }
+    }
+
     /// Returns an iterator over the slab
     #[inline]
     pub fn iter(&self) -> SlabIter<T> {
@@ -129,10 +231,19 @@ impl<T> Slab<T> {
     /// Panics if the host system is out of memory
     #[inline]
     fn reallocate(&mut self) {
+        // ZSTs are never backed by a real allocation, so there is nothing to
+        // grow; capacity stays effectively unbounded.
+        if mem::size_of::<T>() == 0 { return; }
+
         let new_capacity = if self.capacity != 0 { self.capacity * 2 } else { 1 };
-        let maybe_ptr = unsafe {
-            libc::realloc(self.mem as *mut libc::c_void,
-                          (mem::size_of::<T>() * new_capacity)) as *mut T
+        let maybe_ptr = if self.capacity != 0 {
+            unsafe {
+                realloc(self.mem as *mut u8,
+                        Self::layout(self.capacity),
+                        mem::size_of::<T>() * new_capacity) as *mut T
+            }
+        } else {
+            unsafe { alloc(Self::layout(new_capacity)) as *mut T }
         };
 
         if maybe_ptr.is_null() {
@@ -142,6 +253,64 @@ impl<T> Slab<T> {
         self.capacity = new_capacity;
         self.mem = maybe_ptr;
     }
+
+    /// Reallocates the backing store to exactly `new_capacity` elements.
+    ///
+    /// `new_capacity` must be at least `self.len`. A no-op for ZSTs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the host system is out of memory
+    fn resize_to(&mut self, new_capacity: usize) {
+        if mem::size_of::<T>() == 0 || new_capacity == self.capacity { return; }
+
+        let maybe_ptr = if new_capacity == 0 {
+            if self.capacity != 0 {
+                unsafe { dealloc(self.mem as *mut u8, Self::layout(self.capacity)) };
+            }
+            NonNull::<T>::dangling().as_ptr()
+        } else if self.capacity != 0 {
+            unsafe {
+                realloc(self.mem as *mut u8,
+                        Self::layout(self.capacity),
+                        mem::size_of::<T>() * new_capacity) as *mut T
+            }
+        } else {
+            unsafe { alloc(Self::layout(new_capacity)) as *mut T }
+        };
+
+        if maybe_ptr.is_null() && new_capacity != 0 {
+            panic!("Unable to allocate new capacity")
+        }
+
+        self.capacity = new_capacity;
+        self.mem = maybe_ptr;
+    }
+}
+
+impl<T> Extend<T> for Slab<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        let (lower, _) = iter.size_hint();
+        if lower > 0 {
+            let needed = self.len + lower;
+            if needed > self.capacity { self.resize_to(needed); }
+        }
+
+        for elem in iter {
+            self.insert(elem);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for Slab<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Slab<T> {
+        let iter = iter.into_iter();
+        let (lower, _) = iter.size_hint();
+        let mut slab = Slab::with_capacity(lower);
+        slab.extend(iter);
+        slab
+    }
 }
 
 impl<T> Drop for Slab<T> {
@@ -153,7 +322,11 @@ impl<T> Drop for Slab<T> {
             }
         }
 
-        unsafe { libc::free(self.mem as *mut _ as *mut libc::c_void) };
+        // ZSTs and zero-capacity slabs hold no allocation, so there is nothing
+        // to hand back to the allocator.
+        if mem::size_of::<T>() != 0 && self.capacity != 0 {
+            unsafe { dealloc(self.mem as *mut u8, Self::layout(self.capacity)) };
+        }
     }
 }
 
@@ -203,3 +376,20 @@ impl<'a, T> IntoIterator for &'a mut Slab<T> {
         self.iter_mut()
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::Slab;
+
+    #[test]
+    fn from_fn_populates_every_slot() {
+        let slab = Slab::from_fn(4, |i| i * i);
+
+        assert_eq!(slab.len(), 4);
+        assert_eq!(slab[0], 0);
+        assert_eq!(slab[1], 1);
+        assert_eq!(slab[2], 4);
+        assert_eq!(slab[3], 9);
+    }
+}